@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use crate::state::{InputDestination, State};
+
+pub type Action = fn(&mut State);
+
+/// Built-in named commands that key chords (hard-coded or Lua-bound) can
+/// dispatch to. Lua config files register additional chords against these
+/// same names via `bind(chord, name)`.
+pub fn load_actions() -> HashMap<String, Action> {
+    let mut actions: HashMap<String, Action> = HashMap::new();
+    actions.insert("save_file".to_string(), prompt_save);
+    actions.insert("open_file".to_string(), prompt_open);
+    actions.insert("new_file".to_string(), State::new_file);
+    actions.insert("move_left".to_string(), State::move_cursor_left);
+    actions.insert("move_right".to_string(), State::move_cursor_right);
+    actions.insert("move_up".to_string(), State::move_cursor_up);
+    actions.insert("move_down".to_string(), State::move_cursor_down);
+    actions.insert("goto_line_start".to_string(), State::goto_line_start);
+    actions.insert("goto_line_end".to_string(), State::goto_line_end);
+    actions.insert("goto_file_start".to_string(), State::goto_file_start);
+    actions.insert("goto_file_end".to_string(), State::goto_file_end);
+    actions.insert("undo".to_string(), State::undo);
+    actions.insert("redo".to_string(), State::redo);
+    actions
+}
+
+fn prompt_save(state: &mut State) {
+    state.input_destination = InputDestination::Save;
+}
+
+fn prompt_open(state: &mut State) {
+    state.input_destination = InputDestination::Open;
+}
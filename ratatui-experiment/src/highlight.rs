@@ -0,0 +1,220 @@
+use std::rc::Rc;
+
+use ropey::Rope;
+use syntect::{
+    highlighting::{HighlightIterator, HighlightState, Highlighter as SyntectHighlighter, Style, Theme, ThemeSet},
+    parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet},
+};
+
+/// Per-buffer `syntect` highlighting cache. `states[i]` is the parser/color
+/// state as it stood just *before* line `i`, so re-highlighting after an
+/// edit only has to replay from the changed line downward rather than
+/// re-lexing the whole file.
+pub struct Highlighter {
+    syntax_set: SyntaxSet,
+    // `ThemeSet` doesn't implement `Clone`, and re-parsing the bundled
+    // themes on every clone would be wasteful anyway, so share it behind
+    // an `Rc` instead of owning it outright.
+    theme_set: Rc<ThemeSet>,
+    theme_name: String,
+    syntax_name: Option<String>,
+    states: Vec<(ParseState, HighlightState)>,
+    lines: Vec<Vec<(Style, String)>>,
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Highlighter {
+            syntax_set: SyntaxSet::load_defaults_newlines(),
+            theme_set: Rc::new(ThemeSet::load_defaults()),
+            theme_name: "base16-ocean.dark".to_string(),
+            syntax_name: None,
+            states: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl Clone for Highlighter {
+    /// Clones the configuration (syntax sets, selected theme/syntax) but
+    /// not the highlight cache, which is cheap to rebuild on demand.
+    fn clone(&self) -> Self {
+        Highlighter {
+            syntax_set: self.syntax_set.clone(),
+            theme_set: self.theme_set.clone(),
+            theme_name: self.theme_name.clone(),
+            syntax_name: self.syntax_name.clone(),
+            states: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Highlighter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Highlighter")
+            .field("theme_name", &self.theme_name)
+            .field("syntax_name", &self.syntax_name)
+            .finish()
+    }
+}
+
+impl Highlighter {
+    /// Selects a theme by name from the bundled `ThemeSet`. Unknown names
+    /// are ignored, leaving the current theme active.
+    pub fn set_theme(&mut self, name: &str) {
+        if self.theme_set.themes.contains_key(name) {
+            self.theme_name = name.to_string();
+            self.reset();
+        }
+    }
+
+    /// Picks a syntax definition from the current file's extension,
+    /// falling back to plain text.
+    pub fn set_file_name(&mut self, file_name: Option<&str>) {
+        self.syntax_name = file_name
+            .and_then(|name| name.rsplit('.').next())
+            .and_then(|extension| self.syntax_set.find_syntax_by_extension(extension))
+            .map(|syntax| syntax.name.clone());
+        self.reset();
+    }
+
+    /// Invalidates the cache from `line` onward (e.g. after editing that
+    /// line), keeping everything before it so a later render resumes
+    /// instead of re-lexing from the top of the file.
+    pub fn mark_dirty(&mut self, line: usize) {
+        self.lines.truncate(line);
+        self.states.truncate(line + 1);
+    }
+
+    fn reset(&mut self) {
+        self.lines.clear();
+        self.states.clear();
+    }
+
+    fn theme(&self) -> &Theme {
+        self.theme_set
+            .themes
+            .get(&self.theme_name)
+            .unwrap_or_else(|| self.theme_set.themes.values().next().expect("no themes loaded"))
+    }
+
+    fn syntax(&self) -> &SyntaxReference {
+        self.syntax_name
+            .as_deref()
+            .and_then(|name| self.syntax_set.find_syntax_by_name(name))
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text())
+    }
+
+    /// Returns the styled spans for lines `scroll..scroll + height`,
+    /// computing (and caching) whatever in that range isn't cached yet.
+    pub fn visible_lines(&mut self, rope: &Rope, scroll: usize, height: usize) -> &[Vec<(Style, String)>] {
+        let end = (scroll + height).min(rope.len_lines());
+        self.ensure_highlighted(rope, end);
+        let start = scroll.min(self.lines.len());
+        let end = end.min(self.lines.len());
+        &self.lines[start..end]
+    }
+
+    fn ensure_highlighted(&mut self, rope: &Rope, end: usize) {
+        // Cloned out of `self` so `syntect_highlighter` below doesn't keep
+        // `self` borrowed while the loop pushes into `self.lines`/`self.states`.
+        let theme = self.theme().clone();
+        let syntect_highlighter = SyntectHighlighter::new(&theme);
+        if self.states.is_empty() {
+            let initial = (
+                ParseState::new(self.syntax()),
+                HighlightState::new(&syntect_highlighter, ScopeStack::new()),
+            );
+            self.states.push(initial);
+        }
+        while self.lines.len() < end && self.lines.len() < rope.len_lines() {
+            let idx = self.lines.len();
+            let (mut parse_state, mut highlight_state) = self.states[idx].clone();
+            let line = rope.line(idx).to_string();
+            let ops = parse_state
+                .parse_line(&line, &self.syntax_set)
+                .unwrap_or_default();
+            let spans: Vec<(Style, String)> =
+                HighlightIterator::new(&mut highlight_state, &ops, &line, &syntect_highlighter)
+                    .map(|(style, text)| (style, text.to_string()))
+                    .collect();
+            self.lines.push(spans);
+            self.states.push((parse_state, highlight_state));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visible_lines_highlights_and_caches_the_requested_window() {
+        let rope = Rope::from_str("fn main() {\n    1;\n}\n");
+        let mut highlighter = Highlighter::default();
+
+        assert_eq!(highlighter.visible_lines(&rope, 0, 2).len(), 2);
+        // one state per line plus the initial state before line 0
+        assert_eq!(highlighter.lines.len(), 2);
+        assert_eq!(highlighter.states.len(), 3);
+    }
+
+    #[test]
+    fn visible_lines_past_the_end_of_the_rope_is_clamped() {
+        let rope = Rope::from_str("one\ntwo\n");
+        let mut highlighter = Highlighter::default();
+
+        assert_eq!(highlighter.visible_lines(&rope, 0, 100).len(), rope.len_lines());
+    }
+
+    #[test]
+    fn mark_dirty_truncates_the_cache_from_the_given_line() {
+        let rope = Rope::from_str("one\ntwo\nthree\n");
+        let mut highlighter = Highlighter::default();
+        highlighter.visible_lines(&rope, 0, 3);
+
+        highlighter.mark_dirty(1);
+
+        assert_eq!(highlighter.lines.len(), 1);
+        assert_eq!(highlighter.states.len(), 2);
+    }
+
+    #[test]
+    fn mark_dirty_then_visible_lines_only_rehighlights_from_that_line() {
+        let rope = Rope::from_str("one\ntwo\nthree\n");
+        let mut highlighter = Highlighter::default();
+        highlighter.visible_lines(&rope, 0, 3);
+
+        highlighter.mark_dirty(2);
+        assert_eq!(highlighter.lines.len(), 2);
+        highlighter.visible_lines(&rope, 0, 3);
+
+        assert_eq!(highlighter.lines.len(), 3);
+        assert_eq!(highlighter.states.len(), 4);
+    }
+
+    #[test]
+    fn set_theme_resets_the_cache_and_changes_the_active_theme() {
+        let rope = Rope::from_str("one\ntwo\n");
+        let mut highlighter = Highlighter::default();
+        highlighter.visible_lines(&rope, 0, 2);
+        assert!(!highlighter.lines.is_empty());
+
+        highlighter.set_theme("base16-eighties.dark");
+
+        assert_eq!(highlighter.theme_name, "base16-eighties.dark");
+        assert!(highlighter.lines.is_empty());
+        assert!(highlighter.states.is_empty());
+    }
+
+    #[test]
+    fn set_theme_with_an_unknown_name_is_ignored() {
+        let mut highlighter = Highlighter::default();
+        let original = highlighter.theme_name.clone();
+
+        highlighter.set_theme("does-not-exist");
+
+        assert_eq!(highlighter.theme_name, original);
+    }
+}
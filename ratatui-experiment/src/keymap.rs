@@ -0,0 +1,109 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use rlua::Lua;
+
+use crate::state::State;
+
+/// Maps a key chord string (e.g. `"ctrl-s"`, `"w"`) to the name of a
+/// registered action.
+pub type Keymap = HashMap<String, String>;
+
+/// Bindings for chords the terminal UI doesn't match directly (arrow keys,
+/// Home/End), which reach their actions through this map instead. Lua config
+/// is overlaid on top of this, so user bindings win.
+pub fn default_keymap() -> Keymap {
+    let mut map = Keymap::new();
+    map.insert("left".to_string(), "move_left".to_string());
+    map.insert("right".to_string(), "move_right".to_string());
+    map.insert("up".to_string(), "move_up".to_string());
+    map.insert("down".to_string(), "move_down".to_string());
+    map.insert("home".to_string(), "goto_line_start".to_string());
+    map.insert("end".to_string(), "goto_line_end".to_string());
+    map.insert("ctrl-home".to_string(), "goto_file_start".to_string());
+    map.insert("ctrl-end".to_string(), "goto_file_end".to_string());
+    map
+}
+
+/// Renders a key event as the chord string used to key a [`Keymap`].
+pub fn chord(event: &KeyEvent) -> String {
+    let mut chord = String::new();
+    if event.modifiers.contains(KeyModifiers::CONTROL) {
+        chord.push_str("ctrl-");
+    }
+    if event.modifiers.contains(KeyModifiers::ALT) {
+        chord.push_str("alt-");
+    }
+    chord.push_str(&key_name(event.code));
+    chord
+}
+
+fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::F(n) => format!("f{n}"),
+        other => format!("{other:?}").to_lowercase(),
+    }
+}
+
+/// Loads `~/.config/editor/init.lua`, if present, and overlays its
+/// `bind(chord, action)` calls onto `state.keymap`. `state` is also exposed
+/// to the script as the `state` global, so it can read `file_contents`/
+/// `cursor` or invoke actions directly via `state:run(name)`/`state:set_theme(name)`.
+///
+/// Lua userdata always owns its value, so the `state` global is a userdata
+/// handle wrapping a clone rather than `state` itself; we keep that handle
+/// around and copy its (possibly script-mutated) contents back into `state`
+/// once the script has run, so `state:run(...)`/`state:set_theme(...)` calls
+/// actually take effect instead of only touching a value that gets dropped.
+///
+/// The handle is created with `create_static_userdata` rather than
+/// `Context::create_userdata`, which additionally requires `T: Send` —
+/// `State` holds a `Highlighter`, and `syntect`'s default regex backend
+/// isn't `Send`. `create_static_userdata` drops that requirement (Lua
+/// can't be sent to another thread while the scope is alive, so it's
+/// sound), at the cost of the handle expiring when the scope returns —
+/// so we read the mutated value back out before that happens.
+pub fn load_user_overlay(state: &mut State) {
+    let Some(path) = config_path() else { return };
+    let Ok(source) = fs::read_to_string(&path) else {
+        return;
+    };
+    let mut overlay = Keymap::new();
+    let mut mutated = state.clone();
+    let result: rlua::Result<()> = Lua::new().context(|ctx| {
+        ctx.scope(|scope| {
+            let userdata = scope.create_static_userdata(state.clone())?;
+            ctx.globals().set("state", userdata.clone())?;
+            let bind = scope.create_function_mut(|_, (chord, action): (String, String)| {
+                overlay.insert(chord, action);
+                Ok(())
+            })?;
+            ctx.globals().set("bind", bind)?;
+            ctx.load(&source).exec()?;
+            mutated = userdata.borrow::<State>()?.clone();
+            Ok(())
+        })
+    });
+    match result {
+        Ok(()) => {
+            *state = mutated;
+            state.keymap.extend(overlay);
+        }
+        Err(err) => eprintln!("failed to load {}: {err}", path.display()),
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".config/editor/init.lua"))
+}
@@ -0,0 +1,17 @@
+/// Maps an opening delimiter to its closing counterpart. Quote chars map to
+/// themselves, since they open and close with the same char.
+pub type PairTable = Vec<(char, char)>;
+
+/// The built-in auto-closing pairs. Kept as plain data (rather than a
+/// `const`) so it can later be replaced wholesale from Lua config, the same
+/// way [`crate::keymap::default_keymap`] is overlaid by user bindings.
+pub fn default_pairs() -> PairTable {
+    vec![
+        ('(', ')'),
+        ('[', ']'),
+        ('{', '}'),
+        ('"', '"'),
+        ('\'', '\''),
+        ('`', '`'),
+    ]
+}
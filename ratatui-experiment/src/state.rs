@@ -1,29 +1,121 @@
-use std::{fmt::Display, fs, io};
+use std::{collections::HashMap, fmt::Display, fs, io};
 
-#[derive(Debug)]
+use rlua::{MetaMethod, ToLua, UserData};
+use ropey::{Rope, RopeSlice};
+
+use crate::{
+    actions::{self, Action},
+    highlight::Highlighter,
+    keymap::{self, Keymap},
+    pairs::{self, PairTable},
+};
+
+#[derive(Debug, Clone)]
 pub struct State {
     pub current_file_name: Option<String>,
     pub open_file_name: Option<String>,
     pub input_destination: InputDestination,
-    pub file_contents: String,
+    pub file_contents: Rope,
     messages: Vec<String>,
     message_visible: bool,
     pub exited: bool,
     pub cursor: usize,
+    sticky_column: Option<usize>,
+    pub scroll: usize,
+    pub mode: Mode,
+    pub(crate) command_line: String,
+    pub actions: HashMap<String, Action>,
+    pub keymap: Keymap,
+    pub(crate) highlighter: Highlighter,
+    pub(crate) pairs: PairTable,
+    undo_stack: Vec<Edit>,
+    redo_stack: Vec<Edit>,
+    /// Set whenever something other than a contiguous run of inserts or
+    /// removes happens (mode change, cursor jump, newline, save), so the
+    /// next edit starts a fresh undo group instead of coalescing.
+    group_broken: bool,
+}
+
+/// One coalesced undo-history entry: replacing `removed` with `inserted` at
+/// `position`. `cursor_before` is where the cursor sat before the group
+/// began, so undo can restore it exactly.
+#[derive(Debug, Clone)]
+struct Edit {
+    position: usize,
+    removed: String,
+    inserted: String,
+    cursor_before: usize,
+}
+
+/// The editor's modal editing state: `Normal` interprets letter keys as
+/// motions/commands, `Insert` types them into the buffer, and `Command`
+/// accumulates a `:`-style command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Command,
+}
+
+/// A cursor position expressed as a line/column pair, derived on demand
+/// from [`State`]'s flat char offset (see [`State::cursor_position`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub line: usize,
+    pub column: usize,
 }
 
 impl Default for State {
     fn default() -> Self {
-        State {
+        let mut state = State {
             current_file_name: None,
             open_file_name: None,
             input_destination: InputDestination::Buffer,
-            file_contents: String::new(),
+            file_contents: Rope::new(),
             messages: Vec::new(),
             message_visible: false,
             exited: false,
             cursor: 0,
-        }
+            sticky_column: None,
+            scroll: 0,
+            mode: Mode::default(),
+            command_line: String::new(),
+            actions: actions::load_actions(),
+            keymap: keymap::default_keymap(),
+            highlighter: Highlighter::default(),
+            pairs: pairs::default_pairs(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            group_broken: true,
+        };
+        keymap::load_user_overlay(&mut state);
+        state
+    }
+}
+
+impl UserData for State {
+    fn add_methods<'lua, T: rlua::UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_meta_method(MetaMethod::Index, |context, state, key: String| {
+            match key.as_str() {
+                "file_contents" => Ok(state.file_contents.to_string().to_lua(context)),
+                "cursor" => Ok(state.cursor.to_lua(context)),
+                _ => Err(rlua::Error::external(format!("unknown field '{key}'"))),
+            }
+        });
+        methods.add_method_mut("run", |_, state, name: String| {
+            Ok(match state.actions.get(&name).copied() {
+                Some(action) => {
+                    action(state);
+                    true
+                }
+                None => false,
+            })
+        });
+        methods.add_method_mut("set_theme", |_, state, name: String| {
+            state.highlighter.set_theme(&name);
+            Ok(())
+        });
     }
 }
 
@@ -48,6 +140,20 @@ impl State {
             Input::ClearMessage => self.clear_message(),
             Input::MoveLeft => self.move_cursor_left(),
             Input::MoveRight => self.move_cursor_right(),
+            Input::MoveUp => self.move_cursor_up(),
+            Input::MoveDown => self.move_cursor_down(),
+            Input::Undo => self.undo(),
+            Input::Redo => self.redo(),
+            Input::RunAction(name) => match self.actions.get(&name).copied() {
+                Some(action) => action(self),
+                None => self.add_message(format!("no binding for action '{name}'")),
+            },
+            Input::Message(message) => self.add_message(message),
+            Input::Escape => {
+                self.break_undo_group();
+                self.command_line.clear();
+                self.mode = Mode::Normal;
+            }
         }
     }
 
@@ -55,8 +161,15 @@ impl State {
         match self.input_destination {
             InputDestination::Buffer => {
                 if self.cursor > 0 {
-                    self.move_cursor_left();
-                    _ = self.file_contents.remove(self.cursor);
+                    // Step left without going through `move_cursor_left`,
+                    // which would break the undo group this backspace is
+                    // meant to join.
+                    self.cursor -= 1;
+                    let deletes_pair = self.deletes_empty_pair();
+                    self.remove_char(self.cursor);
+                    if deletes_pair {
+                        self.remove_char(self.cursor);
+                    }
                 }
             }
             InputDestination::Open => {
@@ -74,12 +187,21 @@ impl State {
 
     fn enter(&mut self) -> Result<(), EditorError> {
         match self.input_destination {
-            InputDestination::Buffer => {
-                self.add_char('\n');
-                Ok(())
-            }
+            InputDestination::Buffer => match self.mode {
+                Mode::Insert => {
+                    self.add_char('\n');
+                    Ok(())
+                }
+                Mode::Command => {
+                    let command = std::mem::take(&mut self.command_line);
+                    self.mode = Mode::Normal;
+                    self.dispatch_command(&command)
+                }
+                Mode::Normal => Ok(()),
+            },
             InputDestination::Save => {
                 let result = self.save_file();
+                self.break_undo_group();
                 self.input_destination = InputDestination::Buffer;
                 self.clear_message();
                 result
@@ -95,10 +217,11 @@ impl State {
 
     fn add_char(&mut self, c: char) {
         match self.input_destination {
-            InputDestination::Buffer => {
-                self.file_contents.insert(self.cursor, c);
-                self.move_cursor_right();
-            }
+            InputDestination::Buffer => match self.mode {
+                Mode::Normal => self.normal_mode_key(c),
+                Mode::Insert => self.insert_char_typed(c),
+                Mode::Command => self.command_line.push(c),
+            },
             InputDestination::Open => self.open_file_name.get_or_insert_with(String::new).push(c),
             InputDestination::Save => self
                 .current_file_name
@@ -106,19 +229,493 @@ impl State {
                 .push(c),
         }
     }
+
+    /// Inserts a char typed in `Mode::Insert`, applying auto-pairing: typing
+    /// an opening delimiter inserts its close and leaves the cursor between
+    /// them, and typing a closing delimiter that's already next under the
+    /// cursor just steps over it instead of inserting a duplicate.
+    ///
+    /// Advances the cursor directly rather than through `move_cursor_right`,
+    /// since that's a user-facing jump that breaks the undo group — the
+    /// cursor moving past what was just typed is part of the same edit.
+    fn insert_char_typed(&mut self, c: char) {
+        if self.char_at_checked(self.cursor) == Some(c)
+            && self.pairs.iter().any(|&(_, close)| close == c)
+        {
+            self.cursor += 1;
+            self.sticky_column = None;
+            return;
+        }
+        if let Some(&(open, close)) = self.pairs.iter().find(|&&(open, _)| open == c) {
+            let is_quote = open == close;
+            if !is_quote || self.at_pair_boundary() {
+                self.insert_char(self.cursor, open);
+                self.cursor += 1;
+                self.insert_char(self.cursor, close);
+                self.sticky_column = None;
+                return;
+            }
+        }
+        self.insert_char(self.cursor, c);
+        self.cursor += 1;
+        self.sticky_column = None;
+    }
+
+    /// Whether the cursor is at the buffer start or the char before it is
+    /// whitespace or a non-quote opening delimiter, so an auto-paired quote
+    /// won't swallow a prose apostrophe.
+    fn at_pair_boundary(&self) -> bool {
+        match self.cursor.checked_sub(1).and_then(|idx| self.char_at_checked(idx)) {
+            None => true,
+            Some(prev) => {
+                prev.is_whitespace()
+                    || self.pairs.iter().any(|&(open, close)| open == prev && open != close)
+            }
+        }
+    }
+
+    /// Like `char_at`, but `None` past the end of the buffer instead of
+    /// panicking — used where the cursor may legitimately be at EOF.
+    fn char_at_checked(&self, idx: usize) -> Option<char> {
+        (idx < self.len_chars()).then(|| self.char_at(idx))
+    }
+
+    /// Whether the char at the cursor opens a pair whose matching close sits
+    /// immediately after it, i.e. backspacing here should delete both.
+    fn deletes_empty_pair(&self) -> bool {
+        let Some(opening) = self.char_at_checked(self.cursor) else {
+            return false;
+        };
+        self.char_at_checked(self.cursor + 1)
+            .is_some_and(|next| self.pairs.iter().any(|&(open, close)| open == opening && close == next))
+    }
+
+    /// Interprets a key typed in `Mode::Normal` as a motion/command rather
+    /// than text to insert.
+    fn normal_mode_key(&mut self, c: char) {
+        match c {
+            'i' => {
+                self.break_undo_group();
+                self.mode = Mode::Insert;
+            }
+            'a' => {
+                self.move_cursor_right();
+                self.mode = Mode::Insert;
+            }
+            ':' => {
+                self.break_undo_group();
+                self.command_line.clear();
+                self.mode = Mode::Command;
+            }
+            'w' => self.move_next_word_start(),
+            'b' => self.move_prev_word_start(),
+            'e' => self.move_next_word_end(),
+            'W' => self.move_next_long_word_start(),
+            'B' => self.move_prev_long_word_start(),
+            'E' => self.move_next_long_word_end(),
+            _ => self.add_message(format!("no normal-mode binding for '{c}'")),
+        }
+    }
+
+    /// Dispatches a `:`-prefixed command line (the leading `:` is implied,
+    /// since entering `Mode::Command` already consumed it).
+    fn dispatch_command(&mut self, command: &str) -> Result<(), EditorError> {
+        let command = command.trim();
+        if command == "w" {
+            let result = self.save_file();
+            self.break_undo_group();
+            result
+        } else if command == "q" {
+            self.exited = true;
+            Ok(())
+        } else if let Some(path) = command.strip_prefix("o ") {
+            self.open_file_name = Some(path.trim().to_string());
+            self.open_file()
+        } else {
+            Err(EditorError::UnknownCommand(command.to_string()))
+        }
+    }
 }
 
 // cursor
 impl State {
-    fn move_cursor_left(&mut self) {
+    pub(crate) fn move_cursor_left(&mut self) {
+        self.break_undo_group();
         self.cursor = self.cursor.saturating_sub(1);
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn move_cursor_right(&mut self) {
+        self.break_undo_group();
+        self.cursor = self.cursor.saturating_add(1).min(self.len_chars());
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn move_cursor_up(&mut self) {
+        self.move_cursor_vertically(-1);
+    }
+
+    pub(crate) fn move_cursor_down(&mut self) {
+        self.move_cursor_vertically(1);
     }
 
-    fn move_cursor_right(&mut self) {
-        self.cursor = self
-            .cursor
-            .saturating_add(1)
-            .min(self.file_contents.chars().count());
+    fn move_cursor_vertically(&mut self, delta: isize) {
+        self.break_undo_group();
+        let Cursor { line, column } = self.cursor_position();
+        let desired_column = self.sticky_column.unwrap_or(column).max(column);
+        let Some(target_line) = line
+            .checked_add_signed(delta)
+            .filter(|&target| target < self.len_lines())
+        else {
+            return;
+        };
+        let target_column = desired_column.min(self.line_len_chars(target_line));
+        self.cursor = self.line_to_char(target_line) + target_column;
+        self.sticky_column = Some(desired_column);
+    }
+
+    pub(crate) fn goto_line_start(&mut self) {
+        self.break_undo_group();
+        let line = self.cursor_position().line;
+        self.cursor = self.line_to_char(line);
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn goto_line_end(&mut self) {
+        self.break_undo_group();
+        let line = self.cursor_position().line;
+        self.cursor = self.line_to_char(line) + self.line_len_chars(line);
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn goto_file_start(&mut self) {
+        self.break_undo_group();
+        self.cursor = 0;
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn goto_file_end(&mut self) {
+        self.break_undo_group();
+        self.cursor = self.len_chars();
+        self.sticky_column = None;
+    }
+
+    /// The cursor's position as a line/column pair, derived from the flat
+    /// char offset so the two can never drift out of sync.
+    pub fn cursor_position(&self) -> Cursor {
+        let line = self.char_to_line(self.cursor);
+        let column = self.cursor - self.line_to_char(line);
+        Cursor { line, column }
+    }
+
+    /// Adjusts `scroll` so the cursor's line stays within a viewport of the
+    /// given height, starting at the `scroll`-th line.
+    pub fn scroll_to_cursor(&mut self, viewport_height: usize) {
+        let line = self.cursor_position().line;
+        if line < self.scroll {
+            self.scroll = line;
+        } else if viewport_height > 0 && line >= self.scroll + viewport_height {
+            self.scroll = line + 1 - viewport_height;
+        }
+    }
+}
+
+// word motions
+impl State {
+    pub(crate) fn move_next_word_start(&mut self) {
+        self.break_undo_group();
+        self.cursor = self.next_word_start(classify_short);
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn move_prev_word_start(&mut self) {
+        self.break_undo_group();
+        self.cursor = self.prev_word_start(classify_short);
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn move_next_word_end(&mut self) {
+        self.break_undo_group();
+        self.cursor = self.next_word_end(classify_short);
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn move_next_long_word_start(&mut self) {
+        self.break_undo_group();
+        self.cursor = self.next_word_start(classify_long);
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn move_prev_long_word_start(&mut self) {
+        self.break_undo_group();
+        self.cursor = self.prev_word_start(classify_long);
+        self.sticky_column = None;
+    }
+
+    pub(crate) fn move_next_long_word_end(&mut self) {
+        self.break_undo_group();
+        self.cursor = self.next_word_end(classify_long);
+        self.sticky_column = None;
+    }
+
+    fn char_at(&self, idx: usize) -> char {
+        self.file_contents.char(idx)
+    }
+
+    /// Skips the run of the starting category under the cursor, then any
+    /// whitespace, landing on the first char of the next run (or buffer end).
+    fn next_word_start(&self, classify: fn(char) -> CharClass) -> usize {
+        let len = self.len_chars();
+        let mut idx = self.cursor;
+        if idx >= len {
+            return len;
+        }
+        let start_class = classify(self.char_at(idx));
+        while idx < len && classify(self.char_at(idx)) == start_class {
+            idx += 1;
+        }
+        while idx < len && classify(self.char_at(idx)) == CharClass::Whitespace {
+            idx += 1;
+        }
+        idx
+    }
+
+    /// Moves at least one char forward, skips whitespace, then advances to
+    /// the last char of the run it lands in.
+    fn next_word_end(&self, classify: fn(char) -> CharClass) -> usize {
+        let len = self.len_chars();
+        if len == 0 {
+            return 0;
+        }
+        let mut idx = (self.cursor + 1).min(len);
+        while idx < len && classify(self.char_at(idx)) == CharClass::Whitespace {
+            idx += 1;
+        }
+        if idx >= len {
+            return len - 1;
+        }
+        let run_class = classify(self.char_at(idx));
+        while idx + 1 < len && classify(self.char_at(idx + 1)) == run_class {
+            idx += 1;
+        }
+        idx
+    }
+
+    /// The mirror of `next_word_start`, scanning backward.
+    fn prev_word_start(&self, classify: fn(char) -> CharClass) -> usize {
+        let mut idx = self.cursor;
+        if idx == 0 {
+            return 0;
+        }
+        idx -= 1;
+        while idx > 0 && classify(self.char_at(idx)) == CharClass::Whitespace {
+            idx -= 1;
+        }
+        if classify(self.char_at(idx)) == CharClass::Whitespace {
+            return 0;
+        }
+        let run_class = classify(self.char_at(idx));
+        while idx > 0 && classify(self.char_at(idx - 1)) == run_class {
+            idx -= 1;
+        }
+        idx
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punct,
+}
+
+fn classify_short(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punct
+    }
+}
+
+/// "Long word" (`WORD` in vim terms): only whitespace vs. non-whitespace.
+fn classify_long(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else {
+        CharClass::Word
+    }
+}
+
+// undo/redo
+impl State {
+    /// Undoes the latest coalesced edit group, restoring the cursor
+    /// position the group started from.
+    pub(crate) fn undo(&mut self) {
+        let Some(edit) = self.undo_stack.pop() else {
+            return;
+        };
+        self.apply_inverse(&edit);
+        self.cursor = edit.cursor_before;
+        self.sticky_column = None;
+        self.group_broken = true;
+        self.redo_stack.push(edit);
+    }
+
+    /// Re-applies the most recently undone edit group.
+    pub(crate) fn redo(&mut self) {
+        let Some(edit) = self.redo_stack.pop() else {
+            return;
+        };
+        self.apply_forward(&edit);
+        self.cursor = edit.position + edit.inserted.chars().count();
+        self.sticky_column = None;
+        self.group_broken = true;
+        self.undo_stack.push(edit);
+    }
+
+    fn apply_forward(&mut self, edit: &Edit) {
+        self.raw_remove(edit.position, edit.removed.chars().count());
+        self.raw_insert(edit.position, &edit.inserted);
+    }
+
+    fn apply_inverse(&mut self, edit: &Edit) {
+        self.raw_remove(edit.position, edit.inserted.chars().count());
+        self.raw_insert(edit.position, &edit.removed);
+    }
+
+    /// Marks that the next edit must not coalesce with the previous one.
+    fn break_undo_group(&mut self) {
+        self.group_broken = true;
+    }
+
+    /// Records (or extends) the undo entry for inserting `c` at `position`.
+    /// Newlines never coalesce, so a run of typing breaks around them.
+    fn record_insert(&mut self, position: usize, c: char) {
+        self.redo_stack.clear();
+        let extends_last = !self.group_broken
+            && c != '\n'
+            && matches!(
+                self.undo_stack.last(),
+                Some(edit) if edit.removed.is_empty()
+                    && position == edit.position + edit.inserted.chars().count()
+            );
+        if extends_last {
+            self.undo_stack.last_mut().unwrap().inserted.push(c);
+        } else {
+            self.undo_stack.push(Edit {
+                position,
+                removed: String::new(),
+                inserted: c.to_string(),
+                cursor_before: position,
+            });
+        }
+        self.group_broken = c == '\n';
+    }
+
+    /// Records (or extends) the undo entry for removing `c` from
+    /// `position`. Two removal patterns coalesce: stepping backward (as
+    /// plain `backspace` does, prepending each new char) and repeating at
+    /// the same position (as deleting an empty bracket pair does, since the
+    /// second char slides into the first's slot — appended instead).
+    fn record_remove(&mut self, position: usize, c: char) {
+        self.redo_stack.clear();
+        if !self.group_broken {
+            if let Some(edit) = self.undo_stack.last_mut().filter(|edit| edit.inserted.is_empty()) {
+                if position == edit.position {
+                    edit.removed.push(c);
+                    return;
+                } else if position + 1 == edit.position {
+                    edit.removed.insert(0, c);
+                    edit.position = position;
+                    return;
+                }
+            }
+        }
+        self.undo_stack.push(Edit {
+            position,
+            removed: c.to_string(),
+            inserted: String::new(),
+            cursor_before: position + 1,
+        });
+        self.group_broken = false;
+    }
+}
+
+// buffer (rope helpers)
+impl State {
+    pub fn insert_char(&mut self, char_idx: usize, c: char) {
+        self.raw_insert(char_idx, &c.to_string());
+        self.sticky_column = None;
+        self.record_insert(char_idx, c);
+    }
+
+    pub fn remove_char(&mut self, char_idx: usize) {
+        let c = self.char_at(char_idx);
+        self.raw_remove(char_idx, 1);
+        self.sticky_column = None;
+        self.record_remove(char_idx, c);
+    }
+
+    fn raw_insert(&mut self, char_idx: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let line = self.char_to_line(char_idx);
+        self.file_contents.insert(char_idx, text);
+        self.highlighter.mark_dirty(line);
+    }
+
+    fn raw_remove(&mut self, char_idx: usize, len_chars: usize) {
+        if len_chars == 0 {
+            return;
+        }
+        let line = self.char_to_line(char_idx);
+        self.file_contents.remove(char_idx..char_idx + len_chars);
+        self.highlighter.mark_dirty(line);
+    }
+
+    pub fn line(&self, line_idx: usize) -> RopeSlice<'_> {
+        self.file_contents.line(line_idx)
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.file_contents.len_chars()
+    }
+
+    pub fn len_lines(&self) -> usize {
+        self.file_contents.len_lines()
+    }
+
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        self.file_contents.char_to_line(char_idx)
+    }
+
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        self.file_contents.line_to_char(line_idx)
+    }
+
+    /// A line's length in chars, excluding its trailing newline (if any).
+    fn line_len_chars(&self, line_idx: usize) -> usize {
+        let len = self.line(line_idx).len_chars();
+        if line_idx + 1 < self.len_lines() {
+            len.saturating_sub(1)
+        } else {
+            len
+        }
+    }
+
+    /// Syntax-highlighted spans for the lines `self.scroll..self.scroll +
+    /// height`, for the UI layer to turn into styled text.
+    pub(crate) fn visible_highlighted_lines(
+        &mut self,
+        height: usize,
+    ) -> Vec<Vec<(syntect::highlighting::Style, String)>> {
+        let scroll = self.scroll;
+        self.highlighter
+            .visible_lines(&self.file_contents, scroll, height)
+            .to_vec()
     }
 }
 
@@ -129,7 +726,7 @@ impl State {
             .current_file_name
             .as_ref()
             .ok_or(EditorError::NoFileSpecified)?;
-        fs::write(path, self.file_contents.as_bytes())?;
+        self.file_contents.write_to(fs::File::create(path)?)?;
         Ok(())
     }
 
@@ -138,13 +735,15 @@ impl State {
             .open_file_name
             .take()
             .ok_or(EditorError::NoFileSpecified)?;
-        self.file_contents = fs::read_to_string(&path)?;
+        self.file_contents = Rope::from_reader(fs::File::open(&path)?)?;
         self.current_file_name = Some(path);
+        self.highlighter.set_file_name(self.current_file_name.as_deref());
         Ok(())
     }
-    fn new_file(&mut self) {
-        self.file_contents.clear();
+    pub(crate) fn new_file(&mut self) {
+        self.file_contents = Rope::new();
         self.current_file_name = None;
+        self.highlighter.set_file_name(None);
     }
 }
 
@@ -167,7 +766,7 @@ impl State {
     }
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub enum InputDestination {
     #[default]
     Buffer,
@@ -187,12 +786,25 @@ pub enum Input {
     ClearMessage,
     MoveLeft,
     MoveRight,
+    MoveUp,
+    MoveDown,
+    Undo,
+    Redo,
+    /// Returns to `Mode::Normal`, discarding any in-progress command line.
+    Escape,
+    /// Dispatches a named entry from `State::actions` (used for chords
+    /// resolved through the keymap rather than hard-coded here).
+    RunAction(String),
+    /// Surfaces a message to the user without otherwise touching state
+    /// (e.g. "no binding for key").
+    Message(String),
 }
 
 #[derive(Debug)]
 enum EditorError {
     NoFileSpecified,
     IoError(io::Error),
+    UnknownCommand(String),
 }
 
 impl From<io::Error> for EditorError {
@@ -209,8 +821,331 @@ impl Display for EditorError {
                 f.write_str("IO error: ")?;
                 err.fmt(f)
             }
+            EditorError::UnknownCommand(command) => {
+                write!(f, "unknown command '{command}'")
+            }
         }
     }
 }
 
 impl std::error::Error for EditorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with(text: &str, cursor: usize) -> State {
+        let mut state = State {
+            file_contents: Rope::from_str(text),
+            ..State::default()
+        };
+        state.cursor = cursor;
+        state
+    }
+
+    #[test]
+    fn next_word_start_skips_run_then_whitespace() {
+        let state = state_with("foo  bar", 0);
+        assert_eq!(state.next_word_start(classify_short), 5);
+    }
+
+    #[test]
+    fn next_word_start_stops_at_punct_run() {
+        let state = state_with("foo::bar", 0);
+        assert_eq!(state.next_word_start(classify_short), 3);
+    }
+
+    #[test]
+    fn next_word_start_long_word_ignores_punct_boundary() {
+        let state = state_with("foo::bar baz", 0);
+        assert_eq!(state.next_word_start(classify_long), 9);
+    }
+
+    #[test]
+    fn next_word_start_at_end_of_buffer_stays_put() {
+        let state = state_with("foo", 3);
+        assert_eq!(state.next_word_start(classify_short), 3);
+    }
+
+    #[test]
+    fn next_word_end_lands_on_last_char_of_run() {
+        let state = state_with("foo bar", 0);
+        assert_eq!(state.next_word_end(classify_short), 2);
+    }
+
+    #[test]
+    fn next_word_end_from_end_of_word_finds_next_run() {
+        let state = state_with("foo bar", 2);
+        assert_eq!(state.next_word_end(classify_short), 6);
+    }
+
+    #[test]
+    fn prev_word_start_skips_whitespace_then_run() {
+        let state = state_with("foo  bar", 8);
+        assert_eq!(state.prev_word_start(classify_short), 5);
+    }
+
+    #[test]
+    fn prev_word_start_from_start_of_buffer_stays_put() {
+        let state = state_with("foo", 0);
+        assert_eq!(state.prev_word_start(classify_short), 0);
+    }
+
+    #[test]
+    fn classify_short_distinguishes_punct_from_word() {
+        assert_eq!(classify_short('a'), CharClass::Word);
+        assert_eq!(classify_short('_'), CharClass::Word);
+        assert_eq!(classify_short(':'), CharClass::Punct);
+        assert_eq!(classify_short(' '), CharClass::Whitespace);
+    }
+
+    #[test]
+    fn classify_long_treats_punct_as_word() {
+        assert_eq!(classify_long(':'), CharClass::Word);
+        assert_eq!(classify_long(' '), CharClass::Whitespace);
+    }
+
+    #[test]
+    fn typing_a_run_of_chars_coalesces_into_one_undo_entry() {
+        let mut state = state_with("", 0);
+        state.insert_char(0, 'a');
+        state.insert_char(1, 'b');
+        state.insert_char(2, 'c');
+        assert_eq!(state.undo_stack.len(), 1);
+        assert_eq!(state.undo_stack[0].inserted, "abc");
+    }
+
+    #[test]
+    fn undo_after_typing_a_run_removes_the_whole_run_at_once() {
+        let mut state = state_with("", 0);
+        state.insert_char(0, 'a');
+        state.insert_char(1, 'b');
+        state.insert_char(2, 'c');
+        state.cursor = 3;
+        state.undo();
+        assert_eq!(state.file_contents.to_string(), "");
+        assert_eq!(state.cursor, 0);
+    }
+
+    #[test]
+    fn break_undo_group_stops_further_typing_from_coalescing() {
+        let mut state = state_with("", 0);
+        state.insert_char(0, 'a');
+        state.break_undo_group();
+        state.insert_char(1, 'b');
+        assert_eq!(state.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn newline_breaks_the_undo_group_on_both_sides() {
+        let mut state = state_with("", 0);
+        state.insert_char(0, 'a');
+        state.insert_char(1, '\n');
+        state.insert_char(2, 'b');
+        assert_eq!(state.undo_stack.len(), 3);
+    }
+
+    #[test]
+    fn record_remove_coalesces_stepping_backward_like_backspace() {
+        let mut state = state_with("abc", 3);
+        state.remove_char(2);
+        state.remove_char(1);
+        state.remove_char(0);
+        assert_eq!(state.undo_stack.len(), 1);
+        assert_eq!(state.undo_stack[0].removed, "abc");
+    }
+
+    #[test]
+    fn redo_reapplies_an_undone_edit() {
+        let mut state = state_with("", 0);
+        state.insert_char(0, 'a');
+        state.insert_char(1, 'b');
+        state.cursor = 2;
+        state.undo();
+        state.redo();
+        assert_eq!(state.file_contents.to_string(), "ab");
+        assert_eq!(state.cursor, 2);
+    }
+
+    #[test]
+    fn typing_an_opener_inserts_its_closer_and_lands_between_them() {
+        let mut state = state_with("", 0);
+        state.insert_char_typed('(');
+        assert_eq!(state.file_contents.to_string(), "()");
+        assert_eq!(state.cursor, 1);
+    }
+
+    #[test]
+    fn typing_a_closer_already_under_the_cursor_steps_over_it() {
+        let mut state = state_with("()", 1);
+        state.insert_char_typed(')');
+        assert_eq!(state.file_contents.to_string(), "()");
+        assert_eq!(state.cursor, 2);
+    }
+
+    #[test]
+    fn typing_a_closer_not_under_the_cursor_inserts_a_duplicate() {
+        let mut state = state_with("", 0);
+        state.insert_char_typed(')');
+        assert_eq!(state.file_contents.to_string(), ")");
+        assert_eq!(state.cursor, 1);
+    }
+
+    #[test]
+    fn quote_after_a_word_char_does_not_auto_pair() {
+        let mut state = state_with("foo", 3);
+        state.insert_char_typed('\'');
+        assert_eq!(state.file_contents.to_string(), "foo'");
+        assert_eq!(state.cursor, 4);
+    }
+
+    #[test]
+    fn quote_at_a_pair_boundary_auto_pairs() {
+        let mut state = state_with("", 0);
+        state.insert_char_typed('\'');
+        assert_eq!(state.file_contents.to_string(), "''");
+        assert_eq!(state.cursor, 1);
+    }
+
+    #[test]
+    fn deletes_empty_pair_is_true_only_between_a_matching_pair() {
+        let state = state_with("()", 0);
+        assert!(state.deletes_empty_pair());
+        let state = state_with("(a)", 0);
+        assert!(!state.deletes_empty_pair());
+    }
+
+    #[test]
+    fn backspace_between_an_empty_pair_removes_both_chars() {
+        let mut state = state_with("()", 1);
+        state.backspace();
+        assert_eq!(state.file_contents.to_string(), "");
+        assert_eq!(state.cursor, 0);
+    }
+
+    #[test]
+    fn len_chars_and_len_lines_reflect_the_rope_contents() {
+        let state = state_with("foo\nbar\nbaz", 0);
+        assert_eq!(state.len_chars(), 11);
+        assert_eq!(state.len_lines(), 3);
+    }
+
+    #[test]
+    fn char_to_line_and_line_to_char_round_trip() {
+        let state = state_with("foo\nbar\nbaz", 0);
+        assert_eq!(state.char_to_line(5), 1);
+        assert_eq!(state.line_to_char(1), 4);
+        assert_eq!(state.line_to_char(state.char_to_line(9)), 8);
+    }
+
+    #[test]
+    fn line_len_chars_excludes_the_trailing_newline_except_on_the_last_line() {
+        let state = state_with("foo\nbarbaz", 0);
+        assert_eq!(state.line_len_chars(0), 3);
+        assert_eq!(state.line_len_chars(1), 6);
+    }
+
+    #[test]
+    fn insert_char_splits_the_rope_across_a_line_boundary() {
+        let mut state = state_with("foo\nbar", 4);
+        state.insert_char(4, 'X');
+        assert_eq!(state.file_contents.to_string(), "foo\nXbar");
+        assert_eq!(state.line(1).to_string(), "Xbar");
+    }
+
+    #[test]
+    fn cursor_position_tracks_line_and_column_across_a_multiline_rope() {
+        let state = state_with("foo\nbar", 5);
+        assert_eq!(state.cursor_position(), Cursor { line: 1, column: 1 });
+    }
+
+    #[test]
+    fn move_cursor_down_preserves_a_sticky_column_over_a_shorter_line() {
+        let mut state = state_with("foobar\nx\nfoobar", 3);
+        state.move_cursor_down();
+        assert_eq!(state.cursor_position(), Cursor { line: 1, column: 1 });
+        state.move_cursor_down();
+        assert_eq!(state.cursor_position(), Cursor { line: 2, column: 3 });
+    }
+
+    #[test]
+    fn move_cursor_vertically_past_either_edge_of_the_buffer_is_a_no_op() {
+        let mut state = state_with("foo\nbar", 1);
+        state.move_cursor_up();
+        assert_eq!(state.cursor_position(), Cursor { line: 0, column: 1 });
+
+        let mut state = state_with("foo\nbar", 5);
+        state.move_cursor_down();
+        assert_eq!(state.cursor_position(), Cursor { line: 1, column: 1 });
+    }
+
+    #[test]
+    fn scroll_to_cursor_scrolls_down_to_keep_the_cursor_in_view() {
+        let mut state = state_with("a\nb\nc\nd\ne", 0);
+        state.cursor = state.line_to_char(4);
+        state.scroll_to_cursor(2);
+        assert_eq!(state.scroll, 3);
+    }
+
+    #[test]
+    fn scroll_to_cursor_scrolls_up_when_the_cursor_moves_above_the_viewport() {
+        let mut state = state_with("a\nb\nc\nd\ne", 0);
+        state.scroll = 3;
+        state.cursor = state.line_to_char(1);
+        state.scroll_to_cursor(2);
+        assert_eq!(state.scroll, 1);
+    }
+
+    #[test]
+    fn normal_mode_i_and_a_enter_insert_mode() {
+        let mut state = state_with("foo", 0);
+        state.normal_mode_key('i');
+        assert_eq!(state.mode, Mode::Insert);
+        assert_eq!(state.cursor, 0);
+
+        let mut state = state_with("foo", 0);
+        state.normal_mode_key('a');
+        assert_eq!(state.mode, Mode::Insert);
+        assert_eq!(state.cursor, 1);
+    }
+
+    #[test]
+    fn normal_mode_colon_enters_command_mode_and_clears_any_stale_command_line() {
+        let mut state = state_with("foo", 0);
+        state.command_line = "stale".to_string();
+        state.normal_mode_key(':');
+        assert_eq!(state.mode, Mode::Command);
+        assert_eq!(state.command_line, "");
+    }
+
+    #[test]
+    fn normal_mode_unbound_key_surfaces_a_message() {
+        let mut state = state_with("foo", 0);
+        state.normal_mode_key('z');
+        assert_eq!(state.latest_message(), Some("no normal-mode binding for 'z'"));
+    }
+
+    #[test]
+    fn dispatch_command_q_exits() {
+        let mut state = state_with("foo", 0);
+        state.dispatch_command("q").unwrap();
+        assert!(state.exited);
+    }
+
+    #[test]
+    fn dispatch_command_unknown_is_an_error() {
+        let mut state = state_with("foo", 0);
+        let err = state.dispatch_command("bogus").unwrap_err();
+        assert_eq!(err.to_string(), "unknown command 'bogus'");
+    }
+
+    #[test]
+    fn escape_returns_to_normal_mode_and_clears_the_command_line() {
+        let mut state = state_with("foo", 0);
+        state.mode = Mode::Command;
+        state.command_line = ":w".to_string();
+        state.accept_input(Input::Escape);
+        assert_eq!(state.mode, Mode::Normal);
+        assert_eq!(state.command_line, "");
+    }
+}
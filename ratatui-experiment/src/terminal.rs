@@ -10,12 +10,16 @@ use crossterm::{
 };
 use ratatui::{
     prelude::{Constraint, CrosstermBackend, Direction, Layout, Rect},
+    style::Color,
+    text::{Line, Span, Text},
     widgets::{Block, Borders, Paragraph, Wrap},
     Frame, Terminal,
 };
+use syntect::highlighting::Style as SyntectStyle;
 
 use crate::{
-    state::{App, Input, InputDestination},
+    keymap::{self, Keymap},
+    state::{Cursor, Input, InputDestination, Mode, State},
     ui::Ui,
 };
 
@@ -38,15 +42,15 @@ impl Ui for TerminalUi {
         Ok(Self { terminal })
     }
 
-    fn render(&mut self, app: &App) -> Result<(), Self::Error> {
+    fn render(&mut self, app: &mut State) -> Result<(), Self::Error> {
         self.terminal.draw(|frame| render_app(frame, app))?;
         Ok(())
     }
 
-    fn get_input(&mut self) -> Result<Input, Self::Error> {
+    fn get_input(&mut self, app: &State) -> Result<Input, Self::Error> {
         if event::poll(Duration::from_millis(250))? {
             if let Event::Key(event) = event::read()? {
-                return get_char(event);
+                return get_char(event, &app.keymap);
             };
         }
         Ok(Input::None)
@@ -57,30 +61,38 @@ impl Ui for TerminalUi {
     }
 }
 
-fn render_app(frame: &mut Frame, app: &App) {
+fn render_app(frame: &mut Frame, app: &mut State) {
     let mut content_box = frame.size();
+
+    let error_box = app
+        .latest_message()
+        .map(|_| break_off_top(&mut content_box, 3));
+    let dialogue_box = get_message(app).map(|_| break_off_top(&mut content_box, 3));
+
+    // subtract the block's top/bottom border rows to get the visible height,
+    // computed *after* the error/dialogue boxes are carved off `content_box`
+    // above, so scrolling doesn't think those rows are still available.
+    let viewport_height = content_box.height.saturating_sub(2) as usize;
+    app.scroll_to_cursor(viewport_height);
+
+    let lines = app.visible_highlighted_lines(viewport_height);
     let text = paragraph_with_block(
         app.current_file_name.as_deref().unwrap_or("New file"),
-        &app.file_contents,
+        highlighted_text(lines),
     )
     .wrap(Wrap { trim: true });
-    if let Some(dialogue) = get_message(app) {
-        let dialogue_box = break_off_top(&mut content_box, 3);
-        frame.render_widget(dialogue, dialogue_box);
-    }
-    if let Some(error) = app.latest_message() {
-        let error_box = break_off_top(&mut content_box, 3);
+    if let (Some(error), Some(error_box)) = (app.latest_message(), error_box) {
         frame.render_widget(paragraph_with_block("Error", error), error_box);
     }
-    if let Some((label, input)) = get_message(app) {
-        let dialogue_box = break_off_top(&mut content_box, 3);
+    if let (Some((label, input)), Some(dialogue_box)) = (get_message(app), dialogue_box) {
         frame.render_widget(paragraph_with_block(label, input), dialogue_box);
         // adding one because of block borders
         frame.set_cursor(dialogue_box.x + input.len() as u16 + 1, dialogue_box.y + 1)
     } else {
+        let Cursor { line, column } = app.cursor_position();
         frame.set_cursor(
-            content_box.x + app.file_contents.len() as u16 + 1,
-            content_box.y + 1,
+            content_box.x + column as u16 + 1,
+            content_box.y + (line - app.scroll) as u16 + 1,
         )
     }
     frame.render_widget(text, content_box);
@@ -92,25 +104,54 @@ fn break_off_top(rect: &mut Rect, size: u16) -> Rect {
     layouts[0]
 }
 
-fn paragraph_with_block<'a>(block_title: &'a str, content: &'a str) -> Paragraph<'a> {
+fn paragraph_with_block<'a>(block_title: &'a str, content: impl Into<Text<'a>>) -> Paragraph<'a> {
     Paragraph::new(content).block(Block::default().borders(Borders::all()).title(block_title))
 }
 
-fn get_message(app: &App) -> Option<Paragraph<'_>> {
+/// Converts syntax-highlighted spans (already trimmed to the viewport by
+/// `State::visible_highlighted_lines`) into ratatui `Text`.
+fn highlighted_text(lines: Vec<Vec<(SyntectStyle, String)>>) -> Text<'static> {
+    Text::from(
+        lines
+            .into_iter()
+            .map(|spans| {
+                Line::from(
+                    spans
+                        .into_iter()
+                        .map(|(style, text)| Span::styled(text, syntect_to_ratatui_style(style)))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn syntect_to_ratatui_style(style: SyntectStyle) -> ratatui::style::Style {
+    ratatui::style::Style::default().fg(Color::Rgb(
+        style.foreground.r,
+        style.foreground.g,
+        style.foreground.b,
+    ))
+}
+
+fn get_message(app: &State) -> Option<(&'static str, &str)> {
     match app.input_destination {
-        InputDestination::Buffer => None,
-        InputDestination::Open => Some(paragraph_with_block(
+        InputDestination::Buffer => match app.mode {
+            Mode::Command => Some((":", app.command_line.as_str())),
+            Mode::Normal | Mode::Insert => None,
+        },
+        InputDestination::Open => Some((
             "Open file...",
             app.open_file_name.as_deref().unwrap_or(""),
         )),
-        InputDestination::Save => Some(paragraph_with_block(
+        InputDestination::Save => Some((
             "Save as...",
             app.current_file_name.as_deref().unwrap_or(""),
         )),
     }
 }
 
-fn get_char(event: KeyEvent) -> io::Result<Input> {
+fn get_char(event: KeyEvent, keymap: &Keymap) -> io::Result<Input> {
     match event {
         KeyEvent {
             code: KeyCode::Char('c'),
@@ -137,6 +178,19 @@ fn get_char(event: KeyEvent) -> io::Result<Input> {
             modifiers: KeyModifiers::CONTROL,
             ..
         } => Ok(Input::ClearMessage),
+        KeyEvent {
+            code: KeyCode::Char('z'),
+            modifiers: KeyModifiers::CONTROL,
+            ..
+        } => Ok(Input::Undo),
+        KeyEvent {
+            code: KeyCode::Char('y'),
+            modifiers: KeyModifiers::CONTROL,
+            ..
+        } => Ok(Input::Redo),
+        KeyEvent {
+            code: KeyCode::Esc, ..
+        } => Ok(Input::Escape),
         KeyEvent {
             code: KeyCode::Backspace,
             ..
@@ -147,10 +201,18 @@ fn get_char(event: KeyEvent) -> io::Result<Input> {
         } => Ok(Input::Enter),
         KeyEvent {
             code: KeyCode::Char(c),
+            modifiers,
             ..
-        } => Ok(Input::NormalChar(c)),
-        _ => todo!("replace this"),
-        // _ => Err(Error::msg("unrecognised key event")),
+        } if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+            Ok(Input::NormalChar(c))
+        }
+        _ => {
+            let chord = keymap::chord(&event);
+            Ok(match keymap.get(&chord) {
+                Some(action) => Input::RunAction(action.clone()),
+                None => Input::Message(format!("no binding for key '{chord}'")),
+            })
+        }
     }
 }
 